@@ -1,6 +1,7 @@
 use std::borrow::Cow;
+use std::str;
 
-use bstr::BStr;
+use bstr::{BStr, BString};
 
 /// The final component of the path, if it is a normal file.
 ///
@@ -67,6 +68,45 @@ pub fn file_name_ext<'a>(name: &Cow<'a, BStr>) -> Option<Cow<'a, BStr>> {
     })
 }
 
+/// Return a file extension given a path's file name, where the extension
+/// is permitted to contain multiple embedded `.`s.
+///
+/// This is like `file_name_ext`, except instead of starting at the *final*
+/// `.` in the file name, it starts at the *first* one. This means a file
+/// name of `archive.tar.gz` yields an extension of `.tar.gz`, where as
+/// `file_name_ext` would yield `.gz`.
+///
+/// The other semantics are otherwise the same as `file_name_ext`:
+///
+/// * None, if the file name given is empty;
+/// * None, if there is no embedded `.`;
+/// * Otherwise, the portion of the file name starting with the first `.`.
+///
+/// e.g., A file name of `.rs` has a multi extension of `.rs`.
+pub fn file_name_multi_ext<'a>(name: &Cow<'a, BStr>) -> Option<Cow<'a, BStr>> {
+    if name.is_empty() {
+        return None;
+    }
+    let first_dot_at = {
+        let result = name
+            .bytes().enumerate()
+            .find(|&(_, b)| b == b'.')
+            .map(|(i, _)| i);
+        match result {
+            None => return None,
+            Some(i) => i,
+        }
+    };
+    Some(match *name {
+        Cow::Borrowed(name) => Cow::Borrowed(&name[first_dot_at..]),
+        Cow::Owned(ref name) => {
+            let mut name = name.clone();
+            name.drain_bytes(..first_dot_at);
+            Cow::Owned(name)
+        }
+    })
+}
+
 /// Normalizes a path to use `/` as a separator everywhere, even on platforms
 /// that recognize other characters as separators.
 #[cfg(unix)]
@@ -81,6 +121,8 @@ pub fn normalize_path(path: Cow<BStr>) -> Cow<BStr> {
 pub fn normalize_path(mut path: Cow<BStr>) -> Cow<BStr> {
     use std::path::is_separator;
 
+    path = strip_windows_prefix(path);
+
     for i in 0..path.len() {
         if path[i] == b'/' || !is_separator(path[i] as char) {
             continue;
@@ -90,13 +132,138 @@ pub fn normalize_path(mut path: Cow<BStr>) -> Cow<BStr> {
     path
 }
 
+/// Strips (and, where necessary, rewrites) the verbatim and UNC path
+/// prefixes that Windows prepends to canonicalized paths, so that the
+/// separator rewriting below produces the same result regardless of
+/// whether the caller's path went through `std::fs::canonicalize`.
+///
+/// In particular:
+///
+/// * `\\?\UNC\server\share\...` becomes `\\server\share\...`, matching the
+///   non-verbatim UNC form `\\server\share\...` that a user would type.
+/// * `\\?\C:\...` becomes `C:\...`.
+///
+/// Plain UNC paths (`\\server\share\...`) are left untouched here; the
+/// separator rewriting in `normalize_path` turns them into `//server/share`
+/// just like the verbatim UNC form above, once both have been collapsed to
+/// the same shape.
+#[cfg(not(unix))]
+fn strip_windows_prefix(path: Cow<BStr>) -> Cow<BStr> {
+    const VERBATIM_UNC_PREFIX: &[u8] = br"\\?\UNC\";
+    const VERBATIM_PREFIX: &[u8] = br"\\?\";
+
+    if path.starts_with_str(VERBATIM_UNC_PREFIX) {
+        let mut new_path = BString::from(&b"\\\\"[..]);
+        new_path.push_str(&path[VERBATIM_UNC_PREFIX.len()..]);
+        Cow::Owned(new_path)
+    } else if path.starts_with_str(VERBATIM_PREFIX) {
+        match path {
+            Cow::Borrowed(path) => {
+                Cow::Borrowed(&path[VERBATIM_PREFIX.len()..])
+            }
+            Cow::Owned(mut path) => {
+                path.drain_bytes(..VERBATIM_PREFIX.len());
+                Cow::Owned(path)
+            }
+        }
+    } else {
+        path
+    }
+}
+
+/// Applies case folding to `path` so that it can be compared byte-for-byte
+/// against another case-folded path or literal, without compiling a
+/// case-insensitive regex.
+///
+/// If `path` is valid UTF-8, this applies Unicode simple case folding
+/// (approximated here with `char::to_lowercase`, which agrees with simple
+/// case folding for the vast majority of characters). Otherwise, `path` is
+/// folded byte-by-byte using ASCII case folding only, leaving any non-ASCII
+/// bytes untouched.
+///
+/// Like the rest of this module, this preserves the `Cow::Borrowed` variant
+/// when no byte actually needed folding. A cheap byte-level pre-scan avoids
+/// the allocate-and-rebuild path entirely for the common case of a path
+/// that's already lowercase ASCII, but non-ASCII paths still track whether
+/// folding actually changed anything so an already-folded path (e.g.
+/// `"straße"`) stays borrowed too.
+pub fn normalize_path_casefold(path: Cow<BStr>) -> Cow<BStr> {
+    if path.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase()) {
+        return path;
+    }
+    if let Ok(s) = str::from_utf8(&path) {
+        let mut folded = String::with_capacity(s.len());
+        let mut changed = false;
+        for c in s.chars() {
+            for fc in c.to_lowercase() {
+                changed = changed || fc != c;
+                folded.push(fc);
+            }
+        }
+        if !changed {
+            return path;
+        }
+        Cow::Owned(BString::from(folded))
+    } else {
+        if !path.bytes().any(|b| b.is_ascii_uppercase()) {
+            return path;
+        }
+        let mut owned = path.into_owned();
+        for b in owned.iter_mut() {
+            *b = b.to_ascii_lowercase();
+        }
+        Cow::Owned(owned)
+    }
+}
+
+/// Like `file_name`, but returns the case-folded final component of the
+/// path. See `normalize_path_casefold` for the folding semantics.
+pub fn file_name_casefold<'a>(
+    path: &Cow<'a, BStr>,
+) -> Option<Cow<'a, BStr>> {
+    file_name(path).map(normalize_path_casefold)
+}
+
+/// Yields the `/`-separated components of `path`, in order, without
+/// allocating.
+///
+/// Empty segments produced by doubled separators (e.g. `foo//bar`) are
+/// skipped, and a trailing separator does not yield a trailing empty
+/// component. This gives match strategies that need to reason about
+/// component boundaries (anchored prefixes, `**` handling) a way to do so
+/// using borrowed slices instead of a regex.
+///
+/// Callers that need this to behave consistently across platforms should
+/// run `path` through `normalize_path` first, so that `\`-separated
+/// Windows paths are already using `/`.
+pub fn components<'a>(path: &'a BStr) -> impl Iterator<Item = &'a BStr> {
+    path.split(|&b| b == b'/').filter(|s| !s.is_empty()).map(Into::into)
+}
+
+/// Returns true if and only if `path` ends with the given `suffix` and, if
+/// the match doesn't begin at the start of `path`, the byte immediately
+/// preceding the match is a `/`.
+///
+/// This implements the boundary check needed by the `Suffix { component:
+/// true, .. }` match strategy without resorting to a regex: the suffix must
+/// either consume the whole path or be preceded by a component separator.
+pub fn ends_with_component_suffix(path: &Cow<BStr>, suffix: &BStr) -> bool {
+    if !path.ends_with_str(suffix) {
+        return false;
+    }
+    path.len() == suffix.len() || path[path.len() - suffix.len() - 1] == b'/'
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
 
-    use bstr::{B, BString};
+    use bstr::{B, BStr, BString};
 
-    use super::{file_name_ext, normalize_path};
+    use super::{
+        components, ends_with_component_suffix, file_name_ext,
+        file_name_multi_ext, normalize_path, normalize_path_casefold,
+    };
 
     macro_rules! ext {
         ($name:ident, $file_name:expr, $ext:expr) => {
@@ -115,6 +282,24 @@ mod tests {
     ext!(ext4, "", None::<&str>);
     ext!(ext5, "foo", None::<&str>);
 
+    macro_rules! multi_ext {
+        ($name:ident, $file_name:expr, $ext:expr) => {
+            #[test]
+            fn $name() {
+                let bs = BString::from($file_name);
+                let got = file_name_multi_ext(&Cow::Owned(bs));
+                assert_eq!($ext.map(|s| Cow::Borrowed(B(s))), got);
+            }
+        };
+    }
+
+    multi_ext!(multi_ext1, "archive.tar.gz", Some(".tar.gz"));
+    multi_ext!(multi_ext2, "foo.rs", Some(".rs"));
+    multi_ext!(multi_ext3, ".rs", Some(".rs"));
+    multi_ext!(multi_ext4, "..rs", Some("..rs"));
+    multi_ext!(multi_ext5, "", None::<&str>);
+    multi_ext!(multi_ext6, "foo", None::<&str>);
+
     macro_rules! normalize {
         ($name:ident, $path:expr, $expected:expr) => {
             #[test]
@@ -136,4 +321,76 @@ mod tests {
     normalize!(normal4, b"foo\\bar/baz", b"foo\\bar/baz");
     #[cfg(not(unix))]
     normalize!(normal4, b"foo\\bar/baz", b"foo/bar/baz");
+    #[cfg(not(unix))]
+    normalize!(normal5, br"\\?\C:\foo\bar", b"C:/foo/bar");
+    #[cfg(not(unix))]
+    normalize!(
+        normal6,
+        br"\\?\UNC\server\share\foo",
+        b"//server/share/foo"
+    );
+    #[cfg(not(unix))]
+    normalize!(normal7, br"\\server\share\foo", b"//server/share/foo");
+
+    macro_rules! ends_with_comp {
+        ($name:ident, $path:expr, $suffix:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let path = Cow::Borrowed(B($path));
+                let suffix = B($suffix);
+                assert_eq!($expected, ends_with_component_suffix(&path, suffix));
+            }
+        };
+    }
+
+    ends_with_comp!(ends_with_comp1, b"foo/bar", b"bar", true);
+    ends_with_comp!(ends_with_comp2, b"foobar", b"bar", false);
+    ends_with_comp!(ends_with_comp3, b"bar", b"bar", true);
+    ends_with_comp!(ends_with_comp4, b"foo/bar", b"foo/bar", true);
+    ends_with_comp!(ends_with_comp5, b"foo/bar", b"baz", false);
+
+    macro_rules! casefold {
+        ($name:ident, $path:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let bs = BString::from_slice($path);
+                let got = normalize_path_casefold(Cow::Owned(bs));
+                assert_eq!($expected.to_vec(), got.into_owned());
+            }
+        };
+    }
+
+    casefold!(casefold1, b"FOO/BAR", b"foo/bar");
+    casefold!(casefold2, b"foo/bar", b"foo/bar");
+    casefold!(casefold3, "SRC/Straße".as_bytes(), "src/straße".as_bytes());
+    casefold!(casefold4, b"foo\xFFbar", b"foo\xFFbar");
+    casefold!(casefold5, b"foo\xFFBAR", b"foo\xFFbar");
+
+    #[test]
+    fn casefold_borrowed_nonascii() {
+        let path = Cow::Borrowed(B("straße".as_bytes()));
+        let got = normalize_path_casefold(path);
+        assert!(matches!(got, Cow::Borrowed(_)));
+    }
+
+    macro_rules! comps {
+        ($name:ident, $path:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let path = B($path);
+                let got: Vec<&BStr> = components(path).collect();
+                let expected: Vec<&BStr> =
+                    $expected.iter().map(|s| B(*s)).collect();
+                assert_eq!(expected, got);
+            }
+        };
+    }
+
+    comps!(comps1, b"foo/bar", ["foo", "bar"]);
+    comps!(comps2, b"foo", ["foo"]);
+    comps!(comps3, b"foo/bar/", ["foo", "bar"]);
+    comps!(comps4, b"foo//bar", ["foo", "bar"]);
+    comps!(comps5, b"/foo/bar", ["foo", "bar"]);
+    comps!(comps6, b"", [] as [&str; 0]);
+    comps!(comps7, b"/", [] as [&str; 0]);
 }